@@ -0,0 +1,54 @@
+//! Transparent gzip/zlib decompression for stored tile blobs.
+//!
+//! MBTiles vector tiles are almost always compressed before being written to
+//! the `tile_data` blob, so anything that wants to look inside a tile needs
+//! to inflate it first. This sniffs the blob header and decompresses
+//! accordingly, leaving already-raw blobs untouched.
+
+use std::io::Read;
+
+use anyhow::{Context, Result};
+use flate2::read::{GzDecoder, ZlibDecoder};
+
+/// A tile blob after sniffing and (if needed) decompressing it.
+pub struct DecodedBlob {
+    pub data: Vec<u8>,
+    pub compressed: bool,
+}
+
+fn is_gzip(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] == 0x1f && data[1] == 0x8b
+}
+
+fn is_zlib(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] == 0x78
+}
+
+/// Sniff a tile blob's header and decompress it if it looks gzip- or
+/// zlib-encoded; otherwise return the bytes unchanged.
+pub fn decompress_blob(data: &[u8]) -> Result<DecodedBlob> {
+    if is_gzip(data) {
+        let mut out = Vec::new();
+        GzDecoder::new(data)
+            .read_to_end(&mut out)
+            .context("failed to inflate gzip tile blob")?;
+        Ok(DecodedBlob {
+            data: out,
+            compressed: true,
+        })
+    } else if is_zlib(data) {
+        let mut out = Vec::new();
+        ZlibDecoder::new(data)
+            .read_to_end(&mut out)
+            .context("failed to inflate zlib tile blob")?;
+        Ok(DecodedBlob {
+            data: out,
+            compressed: true,
+        })
+    } else {
+        Ok(DecodedBlob {
+            data: data.to_vec(),
+            compressed: false,
+        })
+    }
+}
@@ -1,29 +1,57 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
 
 use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use indicatif::{ProgressBar, ProgressStyle};
 use rusqlite::{params, Connection, OpenFlags};
+use serde::Serialize;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use crate::codec;
+use crate::mvt::{self, LayerGeometryCounts};
+use crate::style::MapboxStyle;
+use crate::tilestore::{TileRow, TileStore};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
 pub struct MbtilesStats {
     pub tile_count: u64,
     pub total_bytes: u64,
     pub max_bytes: u64,
+    pub decompressed_bytes: u64,
+    pub compressed_tile_count: u64,
+    pub layers: HashMap<String, LayerGeometryCounts>,
+}
+
+impl MbtilesStats {
+    /// Ratio of stored (compressed) bytes to decompressed bytes, i.e. how
+    /// much smaller the tiles are on disk than in memory. `1.0` when there's
+    /// nothing to compare, e.g. an empty zoom level or all-raw tiles.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.decompressed_bytes == 0 {
+            1.0
+        } else {
+            self.total_bytes as f64 / self.decompressed_bytes as f64
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct MbtilesZoomStats {
     pub zoom: u8,
     pub stats: MbtilesStats,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct MbtilesReport {
     pub overall: MbtilesStats,
     pub by_zoom: Vec<MbtilesZoomStats>,
 }
 
-fn ensure_mbtiles_path(path: &Path) -> Result<()> {
+pub(crate) fn ensure_mbtiles_path(path: &Path) -> Result<()> {
     let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
     if ext.eq_ignore_ascii_case("mbtiles") {
         Ok(())
@@ -32,12 +60,12 @@ fn ensure_mbtiles_path(path: &Path) -> Result<()> {
     }
 }
 
-fn open_readonly_mbtiles(path: &Path) -> Result<Connection> {
+pub(crate) fn open_readonly_mbtiles(path: &Path) -> Result<Connection> {
     Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
         .with_context(|| format!("failed to open mbtiles: {}", path.display()))
 }
 
-fn apply_read_pragmas(conn: &Connection) -> Result<()> {
+pub(crate) fn apply_read_pragmas(conn: &Connection) -> Result<()> {
     conn.execute_batch(
         "
         PRAGMA query_only = ON;
@@ -50,7 +78,7 @@ fn apply_read_pragmas(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
-fn make_progress_bar(total: u64) -> ProgressBar {
+pub(crate) fn make_progress_bar(total: u64) -> ProgressBar {
     let bar = ProgressBar::new(total);
     bar.set_style(
         ProgressStyle::with_template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
@@ -60,82 +88,214 @@ fn make_progress_bar(total: u64) -> ProgressBar {
     bar
 }
 
-pub fn inspect_mbtiles(path: &Path) -> Result<MbtilesReport> {
-    ensure_mbtiles_path(path)?;
+pub(crate) fn merge_stats(target: &mut MbtilesStats, other: &MbtilesStats) {
+    target.tile_count += other.tile_count;
+    target.total_bytes += other.total_bytes;
+    target.max_bytes = target.max_bytes.max(other.max_bytes);
+    target.decompressed_bytes += other.decompressed_bytes;
+    target.compressed_tile_count += other.compressed_tile_count;
+    for (name, counts) in &other.layers {
+        target.layers.entry(name.clone()).or_default().merge(counts);
+    }
+}
+
+/// Decompress/decode one tile's raw blob and fold it into both the running
+/// `overall` stats and its zoom bucket in `by_zoom`. Shared by every scan
+/// path (the rayon-partitioned MBTiles scan and the format-agnostic
+/// `tilestore::inspect`) so they can't drift apart on what counts as a tile.
+/// A blob that fails to decompress is treated as raw, uninterpretable bytes
+/// rather than aborting the scan.
+pub(crate) fn accumulate_tile_stats(
+    overall: &mut MbtilesStats,
+    by_zoom: &mut BTreeMap<u8, MbtilesStats>,
+    zoom: u8,
+    data: &[u8],
+) {
+    let length = data.len() as u64;
+    let blob = codec::decompress_blob(data).unwrap_or_else(|_| codec::DecodedBlob {
+        data: data.to_vec(),
+        compressed: false,
+    });
+    let decompressed_length = blob.data.len() as u64;
+    let summary = mvt::decode_tile(&blob.data).ok();
+
+    for stats in [overall, by_zoom.entry(zoom).or_default()] {
+        stats.tile_count += 1;
+        stats.total_bytes += length;
+        stats.max_bytes = stats.max_bytes.max(length);
+        stats.decompressed_bytes += decompressed_length;
+        stats.compressed_tile_count += blob.compressed as u64;
+        if let Some(summary) = &summary {
+            summary.merge_into(&mut stats.layers);
+        }
+    }
+}
+
+/// Scan this worker's disjoint slice of `tiles` (`(tile_column + tile_row) %
+/// num_workers == worker_id`) and accumulate its own overall/per-zoom stats.
+///
+/// Each worker opens its own connection: `rusqlite::Connection` isn't
+/// `Sync`, so a single connection can't be shared across the thread pool.
+/// Partitioning goes through `tile_column`/`tile_row` rather than `rowid`
+/// because the MBTiles spec's normalized schema (tiles stored via
+/// `map`/`images` tables and exposed as a `tiles` VIEW, as tippecanoe and
+/// other dedup-friendly generators do) has no `rowid` pseudo-column to
+/// filter on.
+fn scan_partition(
+    path: &Path,
+    worker_id: u64,
+    num_workers: u64,
+    processed: &AtomicU64,
+    progress: &ProgressBar,
+) -> Result<(MbtilesStats, BTreeMap<u8, MbtilesStats>)> {
     let conn = open_readonly_mbtiles(path)?;
     apply_read_pragmas(&conn)?;
 
-    let total_tiles: u64 = conn
-        .query_row("SELECT COUNT(*) FROM tiles", [], |row| row.get(0))
-        .context("failed to read tile count")?;
-    let progress = make_progress_bar(total_tiles);
-
-    let mut overall = MbtilesStats {
-        tile_count: 0,
-        total_bytes: 0,
-        max_bytes: 0,
-    };
+    let mut overall = MbtilesStats::default();
+    let mut by_zoom: BTreeMap<u8, MbtilesStats> = BTreeMap::new();
 
     let mut stmt = conn
-        .prepare("SELECT zoom_level, LENGTH(tile_data) FROM tiles ORDER BY zoom_level")
-        .context("prepare tiles scan")?;
-    let mut rows = stmt.query([]).context("query tiles scan")?;
-
-    let mut by_zoom = Vec::<MbtilesZoomStats>::new();
-    let mut current_zoom: Option<u8> = None;
-    let mut current_stats = MbtilesStats {
-        tile_count: 0,
-        total_bytes: 0,
-        max_bytes: 0,
-    };
+        .prepare(
+            "SELECT zoom_level, tile_data FROM tiles WHERE ((tile_column + tile_row) % ?1) = ?2",
+        )
+        .context("prepare partitioned tiles scan")?;
+    let mut rows = stmt
+        .query(params![num_workers as i64, worker_id as i64])
+        .context("query partitioned tiles scan")?;
 
-    let mut processed: u64 = 0;
     while let Some(row) = rows.next().context("read tile row")? {
         let zoom: u8 = row.get(0)?;
-        let length: u64 = row.get(1)?;
-
-        overall.tile_count += 1;
-        overall.total_bytes += length;
-        overall.max_bytes = overall.max_bytes.max(length);
-
-        match current_zoom {
-            Some(z) if z == zoom => {}
-            Some(z) => {
-                by_zoom.push(MbtilesZoomStats {
-                    zoom: z,
-                    stats: current_stats.clone(),
-                });
-                current_stats = MbtilesStats {
-                    tile_count: 0,
-                    total_bytes: 0,
-                    max_bytes: 0,
-                };
-                current_zoom = Some(zoom);
-            }
-            None => current_zoom = Some(zoom),
+        let data: Vec<u8> = row.get(1)?;
+        accumulate_tile_stats(&mut overall, &mut by_zoom, zoom, &data);
+
+        let done = processed.fetch_add(1, Ordering::Relaxed) + 1;
+        if done % 1000 == 0 {
+            progress.set_position(done);
         }
+    }
 
-        current_stats.tile_count += 1;
-        current_stats.total_bytes += length;
-        current_stats.max_bytes = current_stats.max_bytes.max(length);
+    Ok((overall, by_zoom))
+}
 
-        processed += 1;
-        if processed % 1000 == 0 {
-            progress.set_position(processed);
+/// Scan every tile, fanning blobs out to a rayon thread pool so
+/// decompression and MVT decoding — the CPU-heavy steps once a tile is more
+/// than a byte count — run in parallel on large archives.
+///
+/// Partial results are merged with plain addition/max, which is commutative,
+/// so the final `by_zoom` ordering (driven by a `BTreeMap` key) stays
+/// deterministic no matter which worker finishes first.
+pub fn inspect_mbtiles(path: &Path) -> Result<MbtilesReport> {
+    ensure_mbtiles_path(path)?;
+    let total_tiles: u64 = {
+        let conn = open_readonly_mbtiles(path)?;
+        apply_read_pragmas(&conn)?;
+        conn.query_row("SELECT COUNT(*) FROM tiles", [], |row| row.get(0))
+            .context("failed to read tile count")?
+    };
+
+    let progress = make_progress_bar(total_tiles);
+    let processed = AtomicU64::new(0);
+    let num_workers = (rayon::current_num_threads() as u64).max(1);
+
+    let partials: Mutex<Vec<(MbtilesStats, BTreeMap<u8, MbtilesStats>)>> = Mutex::new(Vec::new());
+    let scan_error: Mutex<Option<anyhow::Error>> = Mutex::new(None);
+
+    rayon::scope(|scope| {
+        for worker_id in 0..num_workers {
+            let partials = &partials;
+            let scan_error = &scan_error;
+            let progress = &progress;
+            let processed = &processed;
+            scope.spawn(move |_| {
+                match scan_partition(path, worker_id, num_workers, processed, progress) {
+                    Ok(result) => partials.lock().unwrap().push(result),
+                    Err(err) => *scan_error.lock().unwrap() = Some(err),
+                }
+            });
         }
+    });
+
+    if let Some(err) = scan_error.into_inner().unwrap() {
+        return Err(err);
     }
 
-    if let Some(z) = current_zoom {
-        by_zoom.push(MbtilesZoomStats {
-            zoom: z,
-            stats: current_stats,
-        });
+    let mut overall = MbtilesStats::default();
+    let mut by_zoom: BTreeMap<u8, MbtilesStats> = BTreeMap::new();
+    for (partial_overall, partial_by_zoom) in partials.into_inner().unwrap() {
+        merge_stats(&mut overall, &partial_overall);
+        for (zoom, stats) in &partial_by_zoom {
+            merge_stats(by_zoom.entry(*zoom).or_default(), stats);
+        }
     }
 
-    progress.set_position(processed);
+    progress.set_position(total_tiles);
     progress.finish_and_clear();
 
-    Ok(MbtilesReport { overall, by_zoom })
+    Ok(MbtilesReport {
+        overall,
+        by_zoom: by_zoom
+            .into_iter()
+            .map(|(zoom, stats)| MbtilesZoomStats { zoom, stats })
+            .collect(),
+    })
+}
+
+/// A [`TileStore`] over an MBTiles SQLite file, for callers that want to
+/// treat MBTiles and PMTiles archives uniformly.
+pub struct MbtilesStore {
+    conn: Connection,
+}
+
+impl MbtilesStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        ensure_mbtiles_path(path)?;
+        let conn = open_readonly_mbtiles(path)?;
+        apply_read_pragmas(&conn)?;
+        Ok(Self { conn })
+    }
+}
+
+impl TileStore for MbtilesStore {
+    fn tile_count(&self) -> Result<u64> {
+        self.conn
+            .query_row("SELECT COUNT(*) FROM tiles", [], |row| row.get(0))
+            .context("failed to read tile count")
+    }
+
+    fn metadata(&self) -> Result<Vec<(String, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name, value FROM metadata")
+            .context("prepare metadata")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .context("query metadata")?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .context("read metadata rows")
+    }
+
+    fn visit_tiles(&self, visit: &mut dyn FnMut(TileRow) -> Result<()>) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT zoom_level, tile_column, tile_row, tile_data FROM tiles ORDER BY zoom_level, tile_column, tile_row",
+            )
+            .context("prepare tiles scan")?;
+        let mut rows = stmt.query([]).context("query tiles scan")?;
+        while let Some(row) = rows.next().context("read tile row")? {
+            let zoom: u8 = row.get(0)?;
+            let x: i64 = row.get(1)?;
+            let y: i64 = row.get(2)?;
+            let data: Vec<u8> = row.get(3)?;
+            visit(TileRow {
+                zoom,
+                x: x as u32,
+                y: y as u32,
+                data,
+            })?;
+        }
+        Ok(())
+    }
 }
 
 pub fn copy_mbtiles(input: &Path, output: &Path) -> Result<()> {
@@ -201,3 +361,154 @@ pub fn copy_mbtiles(input: &Path, output: &Path) -> Result<()> {
     tx.commit().context("commit output")?;
     Ok(())
 }
+
+/// Bytes saved at a single zoom level, and which layers were dropped to get there.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptimizeZoomStats {
+    pub zoom: u8,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub dropped_layer_bytes: HashMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptimizeReport {
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub by_zoom: Vec<OptimizeZoomStats>,
+}
+
+impl OptimizeReport {
+    pub fn bytes_saved(&self) -> u64 {
+        self.input_bytes.saturating_sub(self.output_bytes)
+    }
+}
+
+fn gzip_compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).context("gzip tile data")?;
+    encoder.finish().context("finish gzip tile data")
+}
+
+/// Strip source-layers that the given style never renders at their tile's
+/// zoom level, re-encode the surviving layers, and write the result to
+/// `output`. Metadata is copied verbatim, same as [`copy_mbtiles`].
+pub fn optimize_mbtiles(input: &Path, output: &Path, style: &MapboxStyle) -> Result<OptimizeReport> {
+    ensure_mbtiles_path(input)?;
+    ensure_mbtiles_path(output)?;
+    let input_conn = Connection::open_with_flags(input, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("failed to open input mbtiles: {}", input.display()))?;
+    let mut output_conn = Connection::open(output)
+        .with_context(|| format!("failed to open output mbtiles: {}", output.display()))?;
+
+    output_conn
+        .execute_batch(
+            "
+            CREATE TABLE metadata (name TEXT, value TEXT);
+            CREATE TABLE tiles (
+                zoom_level INTEGER,
+                tile_column INTEGER,
+                tile_row INTEGER,
+                tile_data BLOB
+            );
+            ",
+        )
+        .context("failed to create output schema")?;
+
+    let tx = output_conn.transaction().context("begin output transaction")?;
+
+    {
+        let mut stmt = input_conn
+            .prepare("SELECT name, value FROM metadata")
+            .context("prepare metadata")?;
+        let mut rows = stmt.query([]).context("query metadata")?;
+        while let Some(row) = rows.next().context("read metadata row")? {
+            let name: String = row.get(0)?;
+            let value: String = row.get(1)?;
+            tx.execute(
+                "INSERT INTO metadata (name, value) VALUES (?1, ?2)",
+                params![name, value],
+            )
+            .context("insert metadata")?;
+        }
+    }
+
+    let mut report = OptimizeReport::default();
+    let mut by_zoom = Vec::<OptimizeZoomStats>::new();
+    let mut current_zoom: Option<u8> = None;
+    let mut current_stats = OptimizeZoomStats::default();
+
+    {
+        let mut stmt = input_conn
+            .prepare(
+                "SELECT zoom_level, tile_column, tile_row, tile_data FROM tiles ORDER BY zoom_level, tile_column, tile_row",
+            )
+            .context("prepare tiles")?;
+        let mut rows = stmt.query([]).context("query tiles")?;
+        while let Some(row) = rows.next().context("read tile row")? {
+            let zoom: u8 = row.get(0)?;
+            let x: i64 = row.get(1)?;
+            let y: i64 = row.get(2)?;
+            let data: Vec<u8> = row.get(3)?;
+
+            let input_len = data.len() as u64;
+
+            match current_zoom {
+                Some(cz) if cz == zoom => {}
+                Some(_) => {
+                    by_zoom.push(current_stats.clone());
+                    current_stats = OptimizeZoomStats {
+                        zoom,
+                        ..Default::default()
+                    };
+                }
+                None => current_stats.zoom = zoom,
+            }
+            current_zoom = Some(zoom);
+
+            // A tile that isn't valid MVT (raster tile, empty/corrupt blob)
+            // is passed through unchanged rather than aborting the whole
+            // run, matching how inspect/report tolerate decode failure.
+            let output_data = match codec::decompress_blob(&data).ok() {
+                Some(blob) => match mvt::split_layers(&blob.data).ok() {
+                    Some(layers) if !layers.is_empty() => {
+                        let mut kept = Vec::with_capacity(layers.len());
+                        for layer in layers {
+                            if style.is_layer_visible_on_zoom(&layer.name, zoom) {
+                                kept.push(layer);
+                            } else {
+                                *current_stats
+                                    .dropped_layer_bytes
+                                    .entry(layer.name)
+                                    .or_insert(0) += layer.raw.len() as u64;
+                            }
+                        }
+                        gzip_compress(&mvt::encode_tile(&kept))?
+                    }
+                    _ => data.clone(),
+                },
+                None => data.clone(),
+            };
+            let output_len = output_data.len() as u64;
+
+            tx.execute(
+                "INSERT INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (?1, ?2, ?3, ?4)",
+                params![zoom, x, y, output_data],
+            )
+            .context("insert tile")?;
+
+            report.input_bytes += input_len;
+            report.output_bytes += output_len;
+            current_stats.input_bytes += input_len;
+            current_stats.output_bytes += output_len;
+        }
+    }
+
+    if current_zoom.is_some() {
+        by_zoom.push(current_stats);
+    }
+    report.by_zoom = by_zoom;
+
+    tx.commit().context("commit output")?;
+    Ok(report)
+}
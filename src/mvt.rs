@@ -0,0 +1,340 @@
+//! Minimal decoder for the Mapbox Vector Tile (MVT) protobuf wire format.
+//!
+//! This only reads enough of each tile to classify features by geometry type,
+//! so geometry command streams are skipped rather than walked.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+/// Per-layer feature counts broken down by geometry type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LayerGeometryCounts {
+    pub points: u64,
+    pub linestrings: u64,
+    pub polygons: u64,
+}
+
+impl LayerGeometryCounts {
+    pub fn merge(&mut self, other: &LayerGeometryCounts) {
+        self.points += other.points;
+        self.linestrings += other.linestrings;
+        self.polygons += other.polygons;
+    }
+}
+
+/// Summary of a single decoded tile: geometry counts keyed by source-layer name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TileSummary {
+    pub layers: HashMap<String, LayerGeometryCounts>,
+}
+
+impl TileSummary {
+    pub fn merge_into(&self, target: &mut HashMap<String, LayerGeometryCounts>) {
+        for (name, counts) in &self.layers {
+            target.entry(name.clone()).or_default().merge(counts);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GeomType {
+    Unknown,
+    Point,
+    LineString,
+    Polygon,
+}
+
+impl From<u64> for GeomType {
+    fn from(value: u64) -> Self {
+        match value {
+            1 => GeomType::Point,
+            2 => GeomType::LineString,
+            3 => GeomType::Polygon,
+            _ => GeomType::Unknown,
+        }
+    }
+}
+
+/// A cursor over a single protobuf message, reading tags one at a time.
+struct ProtoReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn read_varint(&mut self) -> Result<u64> {
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .context("unexpected end of protobuf varint")?;
+            self.pos += 1;
+            result |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            anyhow::ensure!(shift < 64, "varint too long");
+        }
+        Ok(result)
+    }
+
+    fn read_tag(&mut self) -> Result<(u64, u64)> {
+        let tag = self.read_varint()?;
+        Ok((tag >> 3, tag & 0x7))
+    }
+
+    fn read_bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_varint()? as usize;
+        let end = self
+            .pos
+            .checked_add(len)
+            .context("length-delimited field overruns buffer")?;
+        let slice = self
+            .data
+            .get(self.pos..end)
+            .context("length-delimited field overruns buffer")?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn skip_field(&mut self, wire_type: u64) -> Result<()> {
+        match wire_type {
+            0 => {
+                self.read_varint()?;
+            }
+            1 => {
+                self.pos = self.pos.checked_add(8).context("fixed64 overruns buffer")?;
+            }
+            2 => {
+                self.read_bytes()?;
+            }
+            5 => {
+                self.pos = self.pos.checked_add(4).context("fixed32 overruns buffer")?;
+            }
+            other => anyhow::bail!("unsupported protobuf wire type {other}"),
+        }
+        Ok(())
+    }
+}
+
+fn decode_feature_geom_type(data: &[u8]) -> Result<GeomType> {
+    let mut reader = ProtoReader::new(data);
+    while !reader.is_empty() {
+        let (field, wire_type) = reader.read_tag()?;
+        if field == 3 && wire_type == 0 {
+            return Ok(GeomType::from(reader.read_varint()?));
+        }
+        reader.skip_field(wire_type)?;
+    }
+    Ok(GeomType::Unknown)
+}
+
+fn decode_layer(data: &[u8]) -> Result<(String, LayerGeometryCounts)> {
+    let mut reader = ProtoReader::new(data);
+    let mut name = String::new();
+    let mut counts = LayerGeometryCounts::default();
+    while !reader.is_empty() {
+        let (field, wire_type) = reader.read_tag()?;
+        match field {
+            1 if wire_type == 2 => {
+                name = String::from_utf8_lossy(reader.read_bytes()?).into_owned();
+            }
+            2 if wire_type == 2 => match decode_feature_geom_type(reader.read_bytes()?)? {
+                GeomType::Point => counts.points += 1,
+                GeomType::LineString => counts.linestrings += 1,
+                GeomType::Polygon => counts.polygons += 1,
+                GeomType::Unknown => {}
+            },
+            _ => reader.skip_field(wire_type)?,
+        }
+    }
+    Ok((name, counts))
+}
+
+/// Decode an MVT tile and summarize feature counts per source-layer.
+///
+/// `data` must already be the raw (decompressed) protobuf bytes.
+pub fn decode_tile(data: &[u8]) -> Result<TileSummary> {
+    let mut reader = ProtoReader::new(data);
+    let mut summary = TileSummary::default();
+    while !reader.is_empty() {
+        let (field, wire_type) = reader.read_tag()?;
+        if field == 3 && wire_type == 2 {
+            let (name, counts) = decode_layer(reader.read_bytes()?)?;
+            summary.layers.entry(name).or_default().merge(&counts);
+        } else {
+            reader.skip_field(wire_type)?;
+        }
+    }
+    Ok(summary)
+}
+
+/// A top-level layer message, borrowed verbatim from the tile buffer.
+///
+/// `raw` is the layer's untouched `keys`/`values`/`extent`/`version`/feature
+/// bytes, so re-wrapping it in [`encode_tile`] reproduces the layer exactly.
+pub struct LayerBlock<'a> {
+    pub name: String,
+    pub raw: &'a [u8],
+}
+
+fn layer_name(data: &[u8]) -> Result<String> {
+    let mut reader = ProtoReader::new(data);
+    while !reader.is_empty() {
+        let (field, wire_type) = reader.read_tag()?;
+        if field == 1 && wire_type == 2 {
+            return Ok(String::from_utf8_lossy(reader.read_bytes()?).into_owned());
+        }
+        reader.skip_field(wire_type)?;
+    }
+    Ok(String::new())
+}
+
+/// Split an MVT tile into its top-level layer messages without touching
+/// their contents, so callers can drop layers and re-encode the rest.
+pub fn split_layers(data: &[u8]) -> Result<Vec<LayerBlock<'_>>> {
+    let mut reader = ProtoReader::new(data);
+    let mut layers = Vec::new();
+    while !reader.is_empty() {
+        let (field, wire_type) = reader.read_tag()?;
+        if field == 3 && wire_type == 2 {
+            let raw = reader.read_bytes()?;
+            layers.push(LayerBlock {
+                name: layer_name(raw)?,
+                raw,
+            });
+        } else {
+            reader.skip_field(wire_type)?;
+        }
+    }
+    Ok(layers)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_tag(out: &mut Vec<u8>, field: u64, wire_type: u64) {
+    write_varint(out, (field << 3) | wire_type);
+}
+
+/// Re-encode a tile from a set of kept layers, each written back verbatim as
+/// a `layers` (tag 3) length-delimited field in its original byte order.
+pub fn encode_tile(layers: &[LayerBlock<'_>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for layer in layers {
+        write_tag(&mut out, 3, 2);
+        write_varint(&mut out, layer.raw.len() as u64);
+        out.extend_from_slice(layer.raw);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer_with_name(name: &str, feature_geom_types: &[u64]) -> Vec<u8> {
+        let mut raw = Vec::new();
+        write_tag(&mut raw, 1, 2);
+        write_varint(&mut raw, name.len() as u64);
+        raw.extend_from_slice(name.as_bytes());
+        for geom_type in feature_geom_types {
+            let mut feature = Vec::new();
+            write_tag(&mut feature, 3, 0);
+            write_varint(&mut feature, *geom_type);
+
+            write_tag(&mut raw, 2, 2);
+            write_varint(&mut raw, feature.len() as u64);
+            raw.extend_from_slice(&feature);
+        }
+        raw
+    }
+
+    fn tile_with_layers(layers: &[Vec<u8>]) -> Vec<u8> {
+        let mut tile = Vec::new();
+        for layer in layers {
+            write_tag(&mut tile, 3, 2);
+            write_varint(&mut tile, layer.len() as u64);
+            tile.extend_from_slice(layer);
+        }
+        tile
+    }
+
+    #[test]
+    fn decode_tile_counts_features_by_geometry_type() {
+        let roads = layer_with_name("roads", &[2, 2, 1]);
+        let buildings = layer_with_name("buildings", &[3]);
+        let tile = tile_with_layers(&[roads, buildings]);
+
+        let summary = decode_tile(&tile).unwrap();
+
+        assert_eq!(
+            summary.layers["roads"],
+            LayerGeometryCounts {
+                points: 1,
+                linestrings: 2,
+                polygons: 0,
+            }
+        );
+        assert_eq!(
+            summary.layers["buildings"],
+            LayerGeometryCounts {
+                points: 0,
+                linestrings: 0,
+                polygons: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn split_layers_then_encode_tile_round_trips() {
+        let roads = layer_with_name("roads", &[2]);
+        let buildings = layer_with_name("buildings", &[3, 3]);
+        let tile = tile_with_layers(&[roads, buildings]);
+
+        let layers = split_layers(&tile).unwrap();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].name, "roads");
+        assert_eq!(layers[1].name, "buildings");
+
+        let re_encoded = encode_tile(&layers);
+        assert_eq!(re_encoded, tile);
+    }
+
+    #[test]
+    fn split_layers_can_drop_a_layer_before_re_encoding() {
+        let roads = layer_with_name("roads", &[2]);
+        let buildings = layer_with_name("buildings", &[3]);
+        let tile = tile_with_layers(&[roads, buildings]);
+
+        let layers = split_layers(&tile).unwrap();
+        let kept: Vec<_> = layers.into_iter().filter(|l| l.name == "roads").collect();
+        let re_encoded = encode_tile(&kept);
+
+        let summary = decode_tile(&re_encoded).unwrap();
+        assert_eq!(summary.layers.len(), 1);
+        assert!(summary.layers.contains_key("roads"));
+    }
+}
@@ -0,0 +1,354 @@
+//! Read-only support for the PMTiles single-file tile archive format.
+//!
+//! A PMTiles file is a fixed 127-byte header, a root directory, zero or more
+//! leaf directories, and a tile-data section, all addressed by byte offsets
+//! within the file. Directory entries are keyed by a Hilbert-curve tile id
+//! that encodes `(z, x, y)`; see <https://github.com/protomaps/PMTiles/blob/main/spec/v3/spec.md>.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use serde_json::Value;
+
+use crate::tilestore::{TileRow, TileStore};
+
+const HEADER_LEN: usize = 127;
+const MAGIC: &[u8] = b"PMTiles";
+
+const COMPRESSION_NONE: u8 = 1;
+const COMPRESSION_GZIP: u8 = 2;
+
+struct Header {
+    root_dir_offset: u64,
+    root_dir_length: u64,
+    json_metadata_offset: u64,
+    json_metadata_length: u64,
+    leaf_dirs_offset: u64,
+    tile_data_offset: u64,
+    num_addressed_tiles: u64,
+    internal_compression: u8,
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn parse_header(data: &[u8]) -> Result<Header> {
+    anyhow::ensure!(data.len() >= HEADER_LEN, "pmtiles file shorter than its header");
+    anyhow::ensure!(&data[0..7] == MAGIC, "not a pmtiles file (bad magic)");
+    let version = data[7];
+    anyhow::ensure!(version == 3, "unsupported pmtiles spec version {version}");
+
+    Ok(Header {
+        root_dir_offset: read_u64(data, 8),
+        root_dir_length: read_u64(data, 16),
+        json_metadata_offset: read_u64(data, 24),
+        json_metadata_length: read_u64(data, 32),
+        leaf_dirs_offset: read_u64(data, 40),
+        tile_data_offset: read_u64(data, 56),
+        num_addressed_tiles: read_u64(data, 72),
+        internal_compression: data[97],
+    })
+}
+
+fn decompress_section(data: &[u8], compression: u8) -> Result<Vec<u8>> {
+    match compression {
+        COMPRESSION_NONE => Ok(data.to_vec()),
+        COMPRESSION_GZIP => {
+            let mut out = Vec::new();
+            GzDecoder::new(data)
+                .read_to_end(&mut out)
+                .context("failed to inflate gzip pmtiles section")?;
+            Ok(out)
+        }
+        other => anyhow::bail!("unsupported pmtiles internal compression code {other}"),
+    }
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut result = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos).context("unexpected end of pmtiles varint")?;
+        *pos += 1;
+        result |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        anyhow::ensure!(shift < 64, "pmtiles varint too long");
+    }
+    Ok(result)
+}
+
+/// A single parsed directory entry: either a pointer to a leaf directory
+/// (`run_length == 0`) or a run of `run_length` tiles sharing one tile-data
+/// byte range.
+struct DirEntry {
+    tile_id: u64,
+    offset: u64,
+    length: u32,
+    run_length: u32,
+}
+
+fn parse_directory(data: &[u8]) -> Result<Vec<DirEntry>> {
+    let mut pos = 0;
+    let num_entries = read_varint(data, &mut pos)? as usize;
+
+    let mut tile_ids = Vec::with_capacity(num_entries);
+    let mut last_id = 0u64;
+    for _ in 0..num_entries {
+        last_id += read_varint(data, &mut pos)?;
+        tile_ids.push(last_id);
+    }
+
+    let mut run_lengths = Vec::with_capacity(num_entries);
+    for _ in 0..num_entries {
+        run_lengths.push(read_varint(data, &mut pos)? as u32);
+    }
+
+    let mut lengths = Vec::with_capacity(num_entries);
+    for _ in 0..num_entries {
+        lengths.push(read_varint(data, &mut pos)? as u32);
+    }
+
+    let mut entries = Vec::with_capacity(num_entries);
+    let mut last_offset = 0u64;
+    for i in 0..num_entries {
+        let value = read_varint(data, &mut pos)?;
+        let offset = if value == 0 {
+            last_offset
+        } else {
+            value - 1
+        };
+        entries.push(DirEntry {
+            tile_id: tile_ids[i],
+            offset,
+            length: lengths[i],
+            run_length: run_lengths[i],
+        });
+        last_offset = offset + lengths[i] as u64;
+    }
+    Ok(entries)
+}
+
+/// Rotate/flip the quadrant a Hilbert `d2xy` step is working in.
+fn hilbert_rotate(n: u64, x: &mut u64, y: &mut u64, rx: u64, ry: u64) {
+    if ry == 0 {
+        if rx == 1 {
+            *x = n - 1 - *x;
+            *y = n - 1 - *y;
+        }
+        std::mem::swap(x, y);
+    }
+}
+
+/// Inverse Hilbert curve mapping: distance along the curve -> `(x, y)`
+/// within an `n`x`n` grid (`n` a power of two).
+fn hilbert_d2xy(n: u64, d: u64) -> (u64, u64) {
+    let mut t = d;
+    let mut x = 0u64;
+    let mut y = 0u64;
+    let mut s = 1u64;
+    while s < n {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        hilbert_rotate(s, &mut x, &mut y, rx, ry);
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+/// Recover `(zoom, x, y)` from a PMTiles tile id: ids are assigned by
+/// counting through zoom levels (`4^z` tiles each) and then walking the
+/// Hilbert curve within the level that contains the id.
+fn tile_id_to_zxy(tile_id: u64) -> Result<(u8, u32, u32)> {
+    let mut tiles_below = 0u64;
+    for zoom in 0u8..32 {
+        let tiles_at_zoom = 1u64 << (2 * zoom as u32);
+        if tiles_below.saturating_add(tiles_at_zoom) > tile_id {
+            let pos = tile_id - tiles_below;
+            let n = 1u64 << zoom;
+            let (x, y) = hilbert_d2xy(n, pos);
+            return Ok((zoom, x as u32, y as u32));
+        }
+        tiles_below += tiles_at_zoom;
+    }
+    anyhow::bail!("pmtiles tile id {tile_id} is out of the representable zoom range")
+}
+
+/// A [`TileStore`] over a PMTiles archive, read entirely into memory since
+/// the format doesn't support incremental row-at-a-time access the way an
+/// MBTiles SQLite query does.
+pub struct PmtilesStore {
+    data: Vec<u8>,
+    header: Header,
+}
+
+impl PmtilesStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("failed to read pmtiles file: {}", path.display()))?;
+        let header = parse_header(&data)?;
+        Ok(Self { data, header })
+    }
+
+    fn section(&self, offset: u64, length: u64) -> Result<&[u8]> {
+        let start = offset as usize;
+        let end = start
+            .checked_add(length as usize)
+            .context("pmtiles section overruns file")?;
+        self.data
+            .get(start..end)
+            .context("pmtiles section overruns file")
+    }
+
+    fn read_directory(&self, offset: u64, length: u64) -> Result<Vec<DirEntry>> {
+        let raw = self.section(offset, length)?;
+        let inflated = decompress_section(raw, self.header.internal_compression)?;
+        parse_directory(&inflated)
+    }
+
+    /// Walk the root directory, recursing into leaf directories, and return
+    /// the flattened list of tile-data entries (`run_length >= 1`).
+    fn flatten_tile_entries(&self) -> Result<Vec<DirEntry>> {
+        let mut flat = Vec::new();
+        let mut pending = self.read_directory(self.header.root_dir_offset, self.header.root_dir_length)?;
+        while let Some(entry) = pending.pop() {
+            if entry.run_length == 0 {
+                let leaf = self.read_directory(
+                    self.header.leaf_dirs_offset + entry.offset,
+                    entry.length as u64,
+                )?;
+                pending.extend(leaf);
+            } else {
+                flat.push(entry);
+            }
+        }
+        Ok(flat)
+    }
+}
+
+impl TileStore for PmtilesStore {
+    fn tile_count(&self) -> Result<u64> {
+        Ok(self.header.num_addressed_tiles)
+    }
+
+    fn metadata(&self) -> Result<Vec<(String, String)>> {
+        if self.header.json_metadata_length == 0 {
+            return Ok(Vec::new());
+        }
+        let raw = self.section(self.header.json_metadata_offset, self.header.json_metadata_length)?;
+        let inflated = decompress_section(raw, self.header.internal_compression)?;
+        let value: Value =
+            serde_json::from_slice(&inflated).context("parse pmtiles json metadata")?;
+        let Some(object) = value.as_object() else {
+            return Ok(Vec::new());
+        };
+        let mut out: HashMap<String, String> = HashMap::with_capacity(object.len());
+        for (key, value) in object {
+            let text = match value.as_str() {
+                Some(s) => s.to_string(),
+                None => value.to_string(),
+            };
+            out.insert(key.clone(), text);
+        }
+        Ok(out.into_iter().collect())
+    }
+
+    fn visit_tiles(&self, visit: &mut dyn FnMut(TileRow) -> Result<()>) -> Result<()> {
+        let entries = self.flatten_tile_entries()?;
+        for entry in entries {
+            let tile_data = self
+                .section(self.header.tile_data_offset + entry.offset, entry.length as u64)?
+                .to_vec();
+            for run in 0..entry.run_length as u64 {
+                let (zoom, x, y) = tile_id_to_zxy(entry.tile_id + run)?;
+                visit(TileRow {
+                    zoom,
+                    x,
+                    y,
+                    data: tile_data.clone(),
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(byte);
+                return;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    #[test]
+    fn tile_id_to_zxy_matches_known_spec_values() {
+        // Zoom 0 has a single tile, id 0.
+        assert_eq!(tile_id_to_zxy(0).unwrap(), (0, 0, 0));
+
+        // Zoom 1 has 4 tiles (ids 1..=4), walked in Hilbert-curve order
+        // over a 2x2 grid: (0,0), (0,1), (1,1), (1,0).
+        assert_eq!(tile_id_to_zxy(1).unwrap(), (1, 0, 0));
+        assert_eq!(tile_id_to_zxy(2).unwrap(), (1, 0, 1));
+        assert_eq!(tile_id_to_zxy(3).unwrap(), (1, 1, 1));
+        assert_eq!(tile_id_to_zxy(4).unwrap(), (1, 1, 0));
+
+        // First tile of zoom 2 (ids 5..=20) sits right after zoom 1 ends.
+        assert_eq!(tile_id_to_zxy(5).unwrap(), (2, 0, 0));
+    }
+
+    #[test]
+    fn hilbert_d2xy_covers_every_cell_of_a_4x4_grid_once() {
+        let mut seen = std::collections::HashSet::new();
+        for d in 0..16 {
+            seen.insert(hilbert_d2xy(4, d));
+        }
+        assert_eq!(seen.len(), 16);
+    }
+
+    #[test]
+    fn parse_directory_round_trips_runs_and_leaf_pointers() {
+        // Two entries: a run of 3 tiles at id 5, offset 0, length 100; then a
+        // leaf-directory pointer (run_length 0) at id 8, offset 100, length 50.
+        let mut raw = Vec::new();
+        write_varint(&mut raw, 2); // num_entries
+        write_varint(&mut raw, 5); // tile_ids: first is absolute
+        write_varint(&mut raw, 3); // second is delta-encoded (8 - 5)
+        write_varint(&mut raw, 3); // run_lengths[0]
+        write_varint(&mut raw, 0); // run_lengths[1] (leaf pointer)
+        write_varint(&mut raw, 100); // lengths[0]
+        write_varint(&mut raw, 50); // lengths[1]
+        write_varint(&mut raw, 1); // offsets[0]: 0 encoded as value-1=0 -> stored as 1
+        write_varint(&mut raw, 0); // offsets[1]: 0 means "continue from last_offset"
+
+        let entries = parse_directory(&raw).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].tile_id, 5);
+        assert_eq!(entries[0].offset, 0);
+        assert_eq!(entries[0].length, 100);
+        assert_eq!(entries[0].run_length, 3);
+
+        assert_eq!(entries[1].tile_id, 8);
+        assert_eq!(entries[1].offset, 100);
+        assert_eq!(entries[1].length, 50);
+        assert_eq!(entries[1].run_length, 0);
+    }
+}
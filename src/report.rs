@@ -0,0 +1,85 @@
+//! JSON rendering of inspection results, for piping into jq, dashboards, or
+//! CI tile-size budget checks.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::codec;
+use crate::mbtiles::{self, MbtilesReport};
+use crate::mvt::{self, LayerGeometryCounts};
+
+/// One row of the per-tile report: `{ "zoom": z, "x": x, "y": y, "bytes": n,
+/// "compressed": bool, "layers": { "roads": { "points": 0, ... }, ... } }`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TileReport {
+    pub zoom: u8,
+    pub x: i64,
+    pub y: i64,
+    pub bytes: u64,
+    pub compressed: bool,
+    pub layers: HashMap<String, LayerGeometryCounts>,
+}
+
+/// Walk every tile in `path`, yielding one [`TileReport`] per row.
+pub fn inspect_mbtiles_per_tile(path: &Path) -> Result<Vec<TileReport>> {
+    mbtiles::ensure_mbtiles_path(path)?;
+    let conn = mbtiles::open_readonly_mbtiles(path)?;
+    mbtiles::apply_read_pragmas(&conn)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT zoom_level, tile_column, tile_row, tile_data FROM tiles ORDER BY zoom_level, tile_column, tile_row",
+        )
+        .context("prepare tiles scan")?;
+    let mut rows = stmt.query([]).context("query tiles scan")?;
+
+    let mut reports = Vec::new();
+    while let Some(row) = rows.next().context("read tile row")? {
+        let zoom: u8 = row.get(0)?;
+        let x: i64 = row.get(1)?;
+        let y: i64 = row.get(2)?;
+        let data: Vec<u8> = row.get(3)?;
+        let bytes = data.len() as u64;
+
+        // A tile that fails to decompress (raster tile, corrupt/raw blob, or
+        // a blob whose first byte happens to collide with the zlib sniff) is
+        // reported as-is rather than aborting the whole dump, matching how
+        // `accumulate_tile_stats` and `optimize_mbtiles` tolerate decode failure.
+        let blob = codec::decompress_blob(&data).unwrap_or_else(|_| codec::DecodedBlob {
+            data: data.clone(),
+            compressed: false,
+        });
+        let layers = mvt::decode_tile(&blob.data)
+            .map(|summary| summary.layers)
+            .unwrap_or_default();
+
+        reports.push(TileReport {
+            zoom,
+            x,
+            y,
+            bytes,
+            compressed: blob.compressed,
+            layers,
+        });
+    }
+    Ok(reports)
+}
+
+/// Serialize one [`TileReport`] per line, ready for streaming into `jq`.
+pub fn per_tile_to_json_lines(rows: &[TileReport]) -> Result<String> {
+    let mut out = String::new();
+    for row in rows {
+        out.push_str(&serde_json::to_string(row).context("serialize tile report")?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Serialize an aggregate [`MbtilesReport`] as a single JSON object, mirroring
+/// the `overall`/`by_zoom` shape the struct already exposes in Rust.
+pub fn summary_to_json(report: &MbtilesReport) -> Result<String> {
+    serde_json::to_string_pretty(report).context("serialize mbtiles report")
+}
@@ -0,0 +1,145 @@
+//! Format-agnostic tile archive access.
+//!
+//! Inspection logic scans `(zoom, x, y, tile_data)` rows without caring
+//! whether they come from an MBTiles SQLite file or a PMTiles flat file;
+//! this trait is the seam between the two.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+
+use crate::mbtiles::{self, MbtilesReport, MbtilesStats, MbtilesStore, MbtilesZoomStats};
+use crate::pmtiles::PmtilesStore;
+
+/// One `(zoom, x, y, tile_data)` row, with `data` left exactly as stored
+/// (still gzip/zlib-compressed, if the archive compresses it).
+pub struct TileRow {
+    pub zoom: u8,
+    pub x: u32,
+    pub y: u32,
+    pub data: Vec<u8>,
+}
+
+/// A tile archive format that can report its metadata and stream its tiles.
+///
+/// `visit_tiles` rather than a `Vec`-returning method so the rows are never
+/// collected into one buffer just to be scanned; implementations call
+/// `visit` once per row as they read it. (`PmtilesStore` still holds the
+/// whole archive file in memory.) `Send` so a `Box<dyn TileStore>` can be
+/// moved to another thread.
+pub trait TileStore: Send {
+    fn tile_count(&self) -> Result<u64>;
+    fn metadata(&self) -> Result<Vec<(String, String)>>;
+    fn visit_tiles(&self, visit: &mut dyn FnMut(TileRow) -> Result<()>) -> Result<()>;
+}
+
+/// Open `path` as whichever tile archive format its extension indicates.
+pub fn open_tile_store(path: &Path) -> Result<Box<dyn TileStore>> {
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    if ext.eq_ignore_ascii_case("mbtiles") {
+        Ok(Box::new(MbtilesStore::open(path)?))
+    } else if ext.eq_ignore_ascii_case("pmtiles") {
+        Ok(Box::new(PmtilesStore::open(path)?))
+    } else {
+        anyhow::bail!("unsupported tile archive extension: {}", path.display());
+    }
+}
+
+/// Inspect any supported tile archive format, dispatching on extension.
+///
+/// MBTiles gets its own native, VIEW-safe, rayon-partitioned SQL scan
+/// ([`mbtiles::inspect_mbtiles`], partitioned by `(tile_column + tile_row) %
+/// N`) that a generic row-at-a-time trait can't match for parallelism, so
+/// `.mbtiles` paths defer to it directly rather than this function running a
+/// second, competing implementation of "scan this mbtiles file" — a scan-level
+/// fix like the VIEW/rowid one would otherwise have to be re-applied in two
+/// places. Every other format (currently just PMTiles) only gives us one
+/// reader (a directory walk can't be split across threads the way a SQL
+/// query can), so a single thread streams rows into a bounded channel and a
+/// small pool of worker threads drains it, each folding its share into its
+/// own stats via [`mbtiles::accumulate_tile_stats`] — the same per-tile logic
+/// the MBTiles scan uses, so the two paths can't drift apart on what counts
+/// as a tile. Workers share one `Receiver` behind a `Mutex` since
+/// `mpsc::Receiver` isn't `Sync`; whichever worker locks it next claims the
+/// next row. Partial results are merged with [`mbtiles::merge_stats`], and
+/// since that's commutative the final `by_zoom` ordering (driven by a
+/// `BTreeMap` key) stays deterministic regardless of which worker processed
+/// which row.
+pub fn inspect(path: &Path) -> Result<MbtilesReport> {
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    if ext.eq_ignore_ascii_case("mbtiles") {
+        return mbtiles::inspect_mbtiles(path);
+    }
+
+    let store = open_tile_store(path)?;
+    let total_tiles = store.tile_count()?;
+    let progress = mbtiles::make_progress_bar(total_tiles);
+    let processed = AtomicU64::new(0);
+
+    let (tx, rx) = mpsc::sync_channel::<TileRow>(256);
+    let rx = Mutex::new(rx);
+    let num_workers = rayon::current_num_threads().max(1);
+
+    let partials: Mutex<Vec<(MbtilesStats, BTreeMap<u8, MbtilesStats>)>> = Mutex::new(Vec::new());
+    let reader_error: Mutex<Option<anyhow::Error>> = Mutex::new(None);
+
+    std::thread::scope(|scope| {
+        for _ in 0..num_workers {
+            let rx = &rx;
+            let partials = &partials;
+            let progress = &progress;
+            let processed = &processed;
+            scope.spawn(move || {
+                let mut overall = MbtilesStats::default();
+                let mut by_zoom: BTreeMap<u8, MbtilesStats> = BTreeMap::new();
+                loop {
+                    let row = match rx.lock().unwrap().recv() {
+                        Ok(row) => row,
+                        Err(_) => break,
+                    };
+                    mbtiles::accumulate_tile_stats(&mut overall, &mut by_zoom, row.zoom, &row.data);
+                    let done = processed.fetch_add(1, Ordering::Relaxed) + 1;
+                    if done % 1000 == 0 {
+                        progress.set_position(done);
+                    }
+                }
+                partials.lock().unwrap().push((overall, by_zoom));
+            });
+        }
+
+        if let Err(err) = store
+            .visit_tiles(&mut |row| tx.send(row).context("send tile row to worker pool"))
+        {
+            *reader_error.lock().unwrap() = Some(err);
+        }
+        drop(tx);
+    });
+
+    if let Some(err) = reader_error.into_inner().unwrap() {
+        return Err(err);
+    }
+
+    let mut overall = MbtilesStats::default();
+    let mut by_zoom: BTreeMap<u8, MbtilesStats> = BTreeMap::new();
+    for (partial_overall, partial_by_zoom) in partials.into_inner().unwrap() {
+        mbtiles::merge_stats(&mut overall, &partial_overall);
+        for (zoom, stats) in &partial_by_zoom {
+            mbtiles::merge_stats(by_zoom.entry(*zoom).or_default(), stats);
+        }
+    }
+
+    progress.set_position(total_tiles);
+    progress.finish_and_clear();
+
+    Ok(MbtilesReport {
+        overall,
+        by_zoom: by_zoom
+            .into_iter()
+            .map(|(zoom, stats)| MbtilesZoomStats { zoom, stats })
+            .collect(),
+    })
+}